@@ -25,6 +25,11 @@ pub struct Config {
     pub password: Option<String>,
     pub is_log_query: bool,
     pub is_explain_query: bool,
+    pub statement_cache_capacity: Option<usize>,
+    pub busy_timeout_ms: Option<u32>,
+    /// Chunk size used by `SqliteConnection::load_in_chunks` to stay under
+    /// SQLite's `SQLITE_MAX_VARIABLE_NUMBER` (~999 by default).
+    pub max_bind_params: usize,
 }
 
 impl Config {
@@ -39,6 +44,9 @@ impl Default for Config {
             password: None,
             is_log_query: false,
             is_explain_query: false,
+            statement_cache_capacity: None,
+            busy_timeout_ms: None,
+            max_bind_params: 900,
         }
     }
 }
@@ -70,6 +78,34 @@ impl ConfigBuilder {
         self
     }
 
+    /// Maximum number of distinct prepared statement shapes kept alive by
+    /// the statement cache at once. Once the cache holds this many entries,
+    /// the least recently used statement is finalized to make room for a
+    /// new one.
+    /// default to None, which keeps every prepared statement for the life
+    /// of the connection
+    pub fn statement_cache_capacity(mut self, capacity: usize) -> Self {
+        self.config.statement_cache_capacity = Some(capacity);
+        self
+    }
+
+    /// How long operations on this connection retry, in milliseconds,
+    /// before giving up with `SQLITE_BUSY` when another connection holds a
+    /// write lock.
+    /// default to None, which returns `SQLITE_BUSY` immediately
+    pub fn busy_timeout_ms(mut self, busy_timeout_ms: u32) -> Self {
+        self.config.busy_timeout_ms = Some(busy_timeout_ms);
+        self
+    }
+
+    /// Chunk size used by `SqliteConnection::load_in_chunks`.
+    /// default to 900, safely under SQLite's default
+    /// `SQLITE_MAX_VARIABLE_NUMBER` of 999
+    pub fn max_bind_params(mut self, max_bind_params: usize) -> Self {
+        self.config.max_bind_params = max_bind_params;
+        self
+    }
+
     pub fn build(self) -> Config {
         self.config
     }
@@ -167,6 +203,33 @@ pub trait Connection: SimpleConnection + Sized + Send {
         }
     }
 
+    /// Executes `f` as an explicit, named savepoint nested within whatever
+    /// transaction is already open (if any). Rolling back only discards
+    /// `f`'s own work, releases the savepoint, and leaves the enclosing
+    /// transaction active, so a caller can retry or discard a sub-step
+    /// without aborting everything around it.
+    ///
+    /// The error returned from the function must implement
+    /// `From<diesel::result::Error>`.
+    fn savepoint<T, E, F>(&self, f: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+        E: From<Error>,
+    {
+        let transaction_manager = self.transaction_manager();
+        try!(transaction_manager.begin_savepoint(self));
+        match f() {
+            Ok(value) => {
+                try!(transaction_manager.release_savepoint(self));
+                Ok(value)
+            }
+            Err(e) => {
+                try!(transaction_manager.rollback_savepoint(self));
+                Err(e)
+            }
+        }
+    }
+
     /// Creates a transaction that will never be committed. This is useful for
     /// tests. Panics if called while inside of a transaction.
     fn begin_test_transaction(&self) -> QueryResult<()> {
@@ -0,0 +1,265 @@
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::ops::{Deref, DerefMut};
+
+use backend::Backend;
+use query_builder::{QueryBuilder, QueryFragment, QueryId};
+use result::QueryResult;
+
+#[derive(Clone, Hash, PartialEq, Eq)]
+pub enum StatementCacheKey<DB: Backend> {
+    Type(TypeId),
+    Sql {
+        sql: String,
+        bind_types: Vec<DB::TypeMetadata>,
+    },
+}
+
+impl<DB: Backend> StatementCacheKey<DB> {
+    pub fn for_source<T: QueryFragment<DB> + QueryId>(
+        source: &T,
+        bind_types: &[DB::TypeMetadata],
+    ) -> QueryResult<Self> {
+        match T::query_id() {
+            Some(id) => Ok(StatementCacheKey::Type(id)),
+            None => {
+                let mut query_builder = DB::QueryBuilder::default();
+                try!(source.to_sql(&mut query_builder));
+                Ok(StatementCacheKey::Sql {
+                    sql: query_builder.finish(),
+                    bind_types: bind_types.into(),
+                })
+            }
+        }
+    }
+}
+
+/// Tracks most-recently-used order for a set of keys, independent of
+/// whatever those keys are cached against. Kept separate from
+/// `StatementCache` so the eviction-order bookkeeping can be unit tested
+/// without needing a real `Backend`/`QueryFragment` to drive it.
+#[derive(Debug)]
+struct LruOrder<K> {
+    order: VecDeque<K>,
+}
+
+impl<K: Eq + Clone> LruOrder<K> {
+    fn new() -> Self {
+        LruOrder {
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Marks `key` as the most recently used entry.
+    fn touch(&mut self, key: &K) {
+        if let Some(pos) = self.order.iter().position(|k| k == key) {
+            let key = self.order.remove(pos).expect("just found this position");
+            self.order.push_back(key);
+        }
+    }
+
+    /// Records a newly inserted key as the most recently used entry.
+    fn record_insert(&mut self, key: K) {
+        self.order.push_back(key);
+    }
+
+    /// Returns the least recently used key to evict, if `current_len` has
+    /// already reached `capacity`; `None` if there's room (or no capacity
+    /// limit at all).
+    fn evict_candidate(&mut self, capacity: Option<usize>, current_len: usize) -> Option<K> {
+        let capacity = match capacity {
+            Some(capacity) => capacity,
+            None => return None,
+        };
+
+        if current_len < capacity {
+            return None;
+        }
+
+        self.order.pop_front()
+    }
+}
+
+/// A cache of prepared statements, keyed by the shape of the query that
+/// produced them.
+///
+/// Queries whose `QueryId` is statically known (no dynamic SQL literals or
+/// bind-value-length-dependent fragments) are cached and reused across
+/// calls; anything else is prepared fresh every time and never enters the
+/// cache. When `capacity` is set, the cache additionally evicts the least
+/// recently used entry (by `cached_statement` hit order) before inserting
+/// past that size, finalizing the evicted statement via its `Drop` impl.
+///
+/// Eviction only happens while inserting a key that is not yet cached. It
+/// does not check whether the entry it selects is currently borrowed out as
+/// a `MaybeCached::Cached`; like the lookup itself, it relies on callers not
+/// reentering `cached_statement` on the same connection while still holding
+/// a previous `MaybeCached::Cached` value.
+#[allow(missing_debug_implementations)]
+pub struct StatementCache<DB: Backend, Statement> {
+    pub cache: RefCell<HashMap<StatementCacheKey<DB>, Statement>>,
+    order: RefCell<LruOrder<StatementCacheKey<DB>>>,
+    capacity: Option<usize>,
+}
+
+impl<DB, Statement> StatementCache<DB, Statement>
+where
+    DB: Backend,
+{
+    /// Create a new cache. `capacity` bounds the number of distinct cached
+    /// statements kept alive at once; `None` keeps today's unbounded
+    /// behavior.
+    pub fn new(capacity: Option<usize>) -> Self {
+        StatementCache {
+            cache: RefCell::new(HashMap::new()),
+            order: RefCell::new(LruOrder::new()),
+            capacity: capacity,
+        }
+    }
+
+    pub fn cached_statement<T, F>(
+        &self,
+        source: &T,
+        bind_types: &[DB::TypeMetadata],
+        prepare_fn: F,
+    ) -> QueryResult<MaybeCached<Statement>>
+    where
+        T: QueryFragment<DB> + QueryId,
+        F: FnOnce(&str) -> QueryResult<Statement>,
+    {
+        let cache_key = try!(StatementCacheKey::for_source(source, bind_types));
+
+        if let StatementCacheKey::Sql { .. } = cache_key {
+            let mut query_builder = DB::QueryBuilder::default();
+            try!(source.to_sql(&mut query_builder));
+            let sql = query_builder.finish();
+            let statement = try!(prepare_fn(&sql));
+            return Ok(MaybeCached::CannotCache(statement));
+        }
+
+        // The returned `&mut Statement` is tied to `&self` rather than to
+        // this `RefCell` borrow, which is only sound as long as callers
+        // don't reenter `cached_statement` on the same connection while
+        // still holding a previous `MaybeCached::Cached` value.
+        let cache = unsafe { &mut *self.cache.as_ptr() };
+
+        if cache.contains_key(&cache_key) {
+            self.order.borrow_mut().touch(&cache_key);
+            let statement = cache
+                .get_mut(&cache_key)
+                .expect("just checked contains_key");
+            return Ok(MaybeCached::Cached(statement));
+        }
+
+        // Eviction must happen here, before any `Entry` into `cache` is
+        // taken below -- evicting while a `Vacant`/`Occupied` entry is
+        // still alive mutates the map out from under it via the raw
+        // pointer above.
+        let evicted = self
+            .order
+            .borrow_mut()
+            .evict_candidate(self.capacity, cache.len());
+        if let Some(lru_key) = evicted {
+            cache.remove(&lru_key);
+        }
+
+        let mut query_builder = DB::QueryBuilder::default();
+        try!(source.to_sql(&mut query_builder));
+        let sql = query_builder.finish();
+        let statement = try!(prepare_fn(&sql));
+        self.order.borrow_mut().record_insert(cache_key.clone());
+        let statement = cache.entry(cache_key).or_insert(statement);
+        Ok(MaybeCached::Cached(statement))
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.cache.borrow().len()
+    }
+}
+
+pub enum MaybeCached<'a, T: 'a> {
+    CannotCache(T),
+    Cached(&'a mut T),
+}
+
+impl<'a, T> Deref for MaybeCached<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match *self {
+            MaybeCached::CannotCache(ref t) => t,
+            MaybeCached::Cached(ref t) => t,
+        }
+    }
+}
+
+impl<'a, T> DerefMut for MaybeCached<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match *self {
+            MaybeCached::CannotCache(ref mut t) => t,
+            MaybeCached::Cached(ref mut t) => t,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LruOrder;
+
+    #[test]
+    fn evict_candidate_is_none_below_capacity() {
+        let mut order = LruOrder::new();
+        order.record_insert(1);
+
+        assert_eq!(None, order.evict_candidate(Some(2), 1));
+    }
+
+    #[test]
+    fn evict_candidate_is_none_without_a_capacity() {
+        let mut order = LruOrder::new();
+        order.record_insert(1);
+        order.record_insert(2);
+        order.record_insert(3);
+
+        assert_eq!(None, order.evict_candidate(None, 3));
+    }
+
+    #[test]
+    fn evict_candidate_picks_the_least_recently_inserted_key() {
+        let mut order = LruOrder::new();
+        order.record_insert(1);
+        order.record_insert(2);
+
+        assert_eq!(Some(1), order.evict_candidate(Some(2), 2));
+    }
+
+    #[test]
+    fn touch_protects_a_key_from_eviction() {
+        let mut order = LruOrder::new();
+        order.record_insert(1);
+        order.record_insert(2);
+
+        // `1` was inserted first, but touching it moves it to the back of
+        // the queue, so `2` becomes the least recently used key instead.
+        order.touch(&1);
+
+        assert_eq!(Some(2), order.evict_candidate(Some(2), 2));
+    }
+
+    #[test]
+    fn evicted_keys_are_forgotten() {
+        let mut order = LruOrder::new();
+        order.record_insert(1);
+        order.record_insert(2);
+
+        let evicted = order.evict_candidate(Some(2), 2);
+        assert_eq!(Some(1), evicted);
+        order.record_insert(3);
+
+        // `1` already left the order on the previous eviction; the next
+        // candidate should be `2`, not `1` again.
+        assert_eq!(Some(2), order.evict_candidate(Some(2), 2));
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::Cell;
+
+use connection::Connection;
+use result::QueryResult;
+
+/// Manages the internal transaction state for a connection.
+#[doc(hidden)]
+pub trait TransactionManager<Conn: Connection> {
+    /// Begin a new transaction, or a savepoint if a transaction is already
+    /// in progress.
+    fn begin_transaction(&self, conn: &Conn) -> QueryResult<()>;
+
+    /// Rollback the innermost transaction or savepoint.
+    fn rollback_transaction(&self, conn: &Conn) -> QueryResult<()>;
+
+    /// Commit the innermost transaction or savepoint.
+    fn commit_transaction(&self, conn: &Conn) -> QueryResult<()>;
+
+    /// Begin a new named savepoint, regardless of whether an enclosing
+    /// transaction has already been opened with `begin_transaction`.
+    fn begin_savepoint(&self, conn: &Conn) -> QueryResult<()>;
+
+    /// Release the innermost savepoint opened with `begin_savepoint`,
+    /// keeping its changes as part of whatever transaction encloses it.
+    fn release_savepoint(&self, conn: &Conn) -> QueryResult<()>;
+
+    /// Roll back to the innermost savepoint opened with `begin_savepoint`,
+    /// discarding only its changes and leaving the enclosing transaction
+    /// (and any savepoints outside of it) active.
+    fn rollback_savepoint(&self, conn: &Conn) -> QueryResult<()>;
+
+    /// The number of transactions and savepoints currently open.
+    fn get_transaction_depth(&self) -> u32;
+}
+
+/// A transaction manager that emits ANSI standard `BEGIN`, `COMMIT`, and
+/// `ROLLBACK`, emulating nested transactions with `SAVEPOINT`.
+#[allow(missing_debug_implementations)]
+pub struct AnsiTransactionManager {
+    transaction_depth: Cell<i32>,
+}
+
+impl AnsiTransactionManager {
+    pub fn new() -> Self {
+        AnsiTransactionManager {
+            transaction_depth: Cell::new(0),
+        }
+    }
+
+    /// Updates the depth counter in response to `query`, only moving it
+    /// when the statement actually ran (or, for a failed `COMMIT`,
+    /// `ROLLBACK` or `RELEASE`, we were already inside a transaction that
+    /// must be assumed broken by the failure), so a failed
+    /// `BEGIN`/`SAVEPOINT` doesn't desynchronize the counter from the
+    /// database's real state.
+    fn change_transaction_depth(&self, by: i32, query: QueryResult<usize>) -> QueryResult<()> {
+        if query.is_ok() || (by < 0 && self.transaction_depth.get() != 0) {
+            self.transaction_depth
+                .set(self.transaction_depth.get() + by);
+        }
+        query.map(|_| ())
+    }
+}
+
+impl<Conn> TransactionManager<Conn> for AnsiTransactionManager
+where
+    Conn: Connection,
+{
+    fn begin_transaction(&self, conn: &Conn) -> QueryResult<()> {
+        let transaction_depth = self.transaction_depth.get();
+        self.change_transaction_depth(
+            1,
+            conn.execute(&begin_transaction_sql(transaction_depth)),
+        )
+    }
+
+    fn rollback_transaction(&self, conn: &Conn) -> QueryResult<()> {
+        let transaction_depth = self.transaction_depth.get();
+        self.change_transaction_depth(
+            -1,
+            conn.execute(&rollback_transaction_sql(transaction_depth)),
+        )
+    }
+
+    fn commit_transaction(&self, conn: &Conn) -> QueryResult<()> {
+        let transaction_depth = self.transaction_depth.get();
+        self.change_transaction_depth(
+            -1,
+            conn.execute(&commit_transaction_sql(transaction_depth)),
+        )
+    }
+
+    fn begin_savepoint(&self, conn: &Conn) -> QueryResult<()> {
+        let depth = self.transaction_depth.get();
+        self.change_transaction_depth(1, conn.execute(&format!("SAVEPOINT sp_{}", depth)))
+    }
+
+    fn release_savepoint(&self, conn: &Conn) -> QueryResult<()> {
+        let depth = self.transaction_depth.get();
+        self.change_transaction_depth(
+            -1,
+            conn.execute(&format!("RELEASE SAVEPOINT sp_{}", depth - 1)),
+        )
+    }
+
+    fn rollback_savepoint(&self, conn: &Conn) -> QueryResult<()> {
+        let depth = self.transaction_depth.get();
+        // `ROLLBACK TO` alone only undoes the savepoint's changes -- the
+        // savepoint itself (and the transaction it's nested in) stays open.
+        // Follow it with `RELEASE` so the savepoint is actually discarded
+        // and `transaction_depth` matches SQLite's real savepoint stack.
+        try!(conn.execute(&format!("ROLLBACK TO SAVEPOINT sp_{}", depth - 1)));
+        self.change_transaction_depth(
+            -1,
+            conn.execute(&format!("RELEASE SAVEPOINT sp_{}", depth - 1)),
+        )
+    }
+
+    fn get_transaction_depth(&self) -> u32 {
+        self.transaction_depth.get() as u32
+    }
+}
+
+fn begin_transaction_sql(depth: i32) -> String {
+    if depth == 0 {
+        "BEGIN".into()
+    } else {
+        format!("SAVEPOINT sp_{}", depth)
+    }
+}
+
+fn rollback_transaction_sql(depth: i32) -> String {
+    if depth == 1 {
+        "ROLLBACK".into()
+    } else {
+        format!("ROLLBACK TO SAVEPOINT sp_{}", depth - 1)
+    }
+}
+
+fn commit_transaction_sql(depth: i32) -> String {
+    if depth == 1 {
+        "COMMIT".into()
+    } else {
+        format!("RELEASE SAVEPOINT sp_{}", depth - 1)
+    }
+}
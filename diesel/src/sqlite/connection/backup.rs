@@ -0,0 +1,86 @@
+extern crate libsqlite3_sys as ffi;
+
+use std::ffi::CString;
+use std::thread::sleep;
+use std::time::Duration;
+
+use result::{DatabaseErrorKind, Error, QueryResult};
+use super::raw::RawConnection;
+
+/// Progress of an in-flight [`backup_to`](struct.SqliteConnection.html#method.backup_to)
+/// operation, reported after every step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupProgress {
+    /// Number of pages still to be copied.
+    pub remaining: i32,
+    /// Total number of pages in the source database, as of the last step.
+    pub pagecount: i32,
+}
+
+/// Copies `source` into `dest`, one step of `pages_per_step` pages at a time.
+///
+/// A `pages_per_step` of a negative number copies the whole database in a
+/// single step. Progress after each step is reported through `progress`, and
+/// `SQLITE_BUSY`/`SQLITE_LOCKED` results (another connection is writing to
+/// `source` or `dest`) are retried after a short sleep so the backup doesn't
+/// block concurrent readers or writers.
+pub fn backup(
+    source: &RawConnection,
+    dest: &RawConnection,
+    pages_per_step: i32,
+    mut progress: Option<&mut FnMut(BackupProgress)>,
+) -> QueryResult<()> {
+    let main = try!(CString::new("main"));
+    let handle = unsafe {
+        ffi::sqlite3_backup_init(
+            dest.internal_connection.as_ptr(),
+            main.as_ptr(),
+            source.internal_connection.as_ptr(),
+            main.as_ptr(),
+        )
+    };
+
+    if handle.is_null() {
+        return backup_error(dest);
+    }
+
+    loop {
+        let result = unsafe { ffi::sqlite3_backup_step(handle, pages_per_step) };
+
+        if let Some(ref mut progress) = progress {
+            let remaining = unsafe { ffi::sqlite3_backup_remaining(handle) };
+            let pagecount = unsafe { ffi::sqlite3_backup_pagecount(handle) };
+            progress(BackupProgress {
+                remaining: remaining,
+                pagecount: pagecount,
+            });
+        }
+
+        match result {
+            ffi::SQLITE_OK => continue,
+            ffi::SQLITE_DONE => break,
+            ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED => {
+                sleep(Duration::from_millis(50));
+                continue;
+            }
+            _ => {
+                unsafe { ffi::sqlite3_backup_finish(handle) };
+                return backup_error(dest);
+            }
+        }
+    }
+
+    let finish_result = unsafe { ffi::sqlite3_backup_finish(handle) };
+    if finish_result == ffi::SQLITE_OK {
+        Ok(())
+    } else {
+        backup_error(dest)
+    }
+}
+
+fn backup_error(dest: &RawConnection) -> QueryResult<()> {
+    Err(Error::DatabaseError(
+        DatabaseErrorKind::__Unknown,
+        Box::new(dest.error_message()),
+    ))
+}
@@ -0,0 +1,162 @@
+extern crate libsqlite3_sys as ffi;
+
+use std::ffi::CString;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::raw as libc;
+use std::rc::Rc;
+
+use result::{DatabaseErrorKind, Error, QueryResult};
+use super::raw::RawConnection;
+
+/// A streaming handle onto a single BLOB/TEXT column value, opened with
+/// [`SqliteConnection::open_blob`](struct.SqliteConnection.html#method.open_blob).
+///
+/// Reads and writes go straight through `sqlite3_blob_read`/`sqlite3_blob_write`
+/// at a tracked byte offset, so arbitrarily large column values can be
+/// streamed without ever materializing the whole thing as a `Vec<u8>`.
+/// Writes cannot change the length of the blob; writing past the end
+/// returns an error instead of growing it.
+///
+/// `offset`/`len` are kept as `i64` so bookkeeping arithmetic can't
+/// overflow even though SQLite's blob API itself is `int`-addressed
+/// (`sqlite3_blob_read`/`write` cap a single blob at `i32::MAX` bytes
+/// regardless); values are narrowed to `c_int` only right at the FFI call.
+#[allow(missing_debug_implementations)]
+pub struct Blob {
+    conn: Rc<RawConnection>,
+    blob: *mut ffi::sqlite3_blob,
+    offset: i64,
+    len: i64,
+}
+
+impl Blob {
+    pub fn open(
+        conn: Rc<RawConnection>,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        read_write: bool,
+    ) -> QueryResult<Self> {
+        let main = try!(CString::new("main"));
+        let table = try!(CString::new(table));
+        let column = try!(CString::new(column));
+        let mut blob = 0 as *mut ffi::sqlite3_blob;
+
+        let result = unsafe {
+            ffi::sqlite3_blob_open(
+                conn.internal_connection.as_ptr(),
+                main.as_ptr(),
+                table.as_ptr(),
+                column.as_ptr(),
+                rowid,
+                read_write as libc::c_int,
+                &mut blob,
+            )
+        };
+
+        if result != ffi::SQLITE_OK {
+            return Err(Error::DatabaseError(
+                DatabaseErrorKind::__Unknown,
+                Box::new(conn.error_message()),
+            ));
+        }
+
+        let len = unsafe { ffi::sqlite3_blob_bytes(blob) };
+
+        Ok(Blob {
+            conn: conn,
+            blob: blob,
+            offset: 0,
+            len: len as i64,
+        })
+    }
+
+    /// The fixed size in bytes of the underlying BLOB value.
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Whether the underlying BLOB value is zero-length.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Read for Blob {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.len - self.offset;
+        if remaining <= 0 {
+            return Ok(0);
+        }
+        let n = ::std::cmp::min(buf.len() as i64, remaining);
+        let result = unsafe {
+            ffi::sqlite3_blob_read(
+                self.blob,
+                buf.as_mut_ptr() as *mut libc::c_void,
+                n as libc::c_int,
+                self.offset as libc::c_int,
+            )
+        };
+        if result != ffi::SQLITE_OK {
+            return Err(io::Error::new(io::ErrorKind::Other, self.conn.error_message()));
+        }
+        self.offset += n;
+        Ok(n as usize)
+    }
+}
+
+impl Write for Blob {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.offset + buf.len() as i64 > self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot write past the end of a BLOB; writes cannot grow or shrink it",
+            ));
+        }
+        let result = unsafe {
+            ffi::sqlite3_blob_write(
+                self.blob,
+                buf.as_ptr() as *const libc::c_void,
+                buf.len() as libc::c_int,
+                self.offset as libc::c_int,
+            )
+        };
+        if result != ffi::SQLITE_OK {
+            return Err(io::Error::new(io::ErrorKind::Other, self.conn.error_message()));
+        }
+        self.offset += buf.len() as i64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for Blob {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_offset = match pos {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.len + offset,
+            SeekFrom::Current(offset) => self.offset + offset,
+        };
+
+        if new_offset < 0 || new_offset > self.len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek position out of bounds of the BLOB",
+            ));
+        }
+
+        self.offset = new_offset;
+        Ok(self.offset as u64)
+    }
+}
+
+impl Drop for Blob {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::sqlite3_blob_close(self.blob);
+        }
+    }
+}
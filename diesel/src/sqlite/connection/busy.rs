@@ -0,0 +1,49 @@
+extern crate libsqlite3_sys as ffi;
+
+use std::os::raw as libc;
+
+use super::raw::RawConnection;
+
+pub type BusyHandlerFn = Fn(i32) -> bool + Send;
+
+/// Applies `sqlite3_busy_timeout(db, ms)`, so operations on `conn` retry
+/// transparently for up to `ms` milliseconds instead of immediately failing
+/// with `SQLITE_BUSY` when another connection holds a write lock.
+///
+/// This replaces any busy handler installed with `set_busy_handler` -- the
+/// two are mutually exclusive in SQLite, the most recently set one wins.
+pub fn set_busy_timeout(conn: &RawConnection, ms: u32) {
+    unsafe {
+        ffi::sqlite3_busy_timeout(conn.internal_connection.as_ptr(), ms as libc::c_int);
+    }
+}
+
+/// Registers `handler` with `sqlite3_busy_handler` and returns the boxed
+/// closure. As with the other hook trampolines, `sqlite3_busy_handler` takes
+/// no destructor, so the returned box must be kept alive by the caller
+/// (stored on `SqliteConnection`) for as long as the handler stays
+/// installed.
+pub fn set_busy_handler<F>(conn: &RawConnection, handler: F) -> Box<Box<BusyHandlerFn>>
+where
+    F: Fn(i32) -> bool + Send + 'static,
+{
+    let boxed: Box<Box<BusyHandlerFn>> = Box::new(Box::new(handler));
+    let user_data = &*boxed as *const Box<BusyHandlerFn> as *mut libc::c_void;
+
+    unsafe {
+        ffi::sqlite3_busy_handler(conn.internal_connection.as_ptr(), Some(trampoline), user_data);
+    }
+
+    boxed
+}
+
+extern "C" fn trampoline(p_arg: *mut libc::c_void, num_prior_invocations: libc::c_int) -> libc::c_int {
+    unsafe {
+        let handler = &*(p_arg as *const Box<BusyHandlerFn>);
+        if handler(num_prior_invocations) {
+            1
+        } else {
+            0
+        }
+    }
+}
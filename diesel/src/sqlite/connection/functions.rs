@@ -0,0 +1,115 @@
+extern crate libsqlite3_sys as ffi;
+
+use std::ffi::CString;
+use std::os::raw as libc;
+use std::panic::{self, AssertUnwindSafe};
+
+use result::{DatabaseErrorKind, Error, QueryResult};
+use super::raw::RawConnection;
+use super::sqlite_value::SqliteValue;
+
+/// Implemented for everything that can be handed back to SQLite as the
+/// result of a user-defined scalar function.
+pub trait ToFunctionResult {
+    unsafe fn set_result(self, ctx: *mut ffi::sqlite3_context);
+}
+
+impl ToFunctionResult for String {
+    unsafe fn set_result(self, ctx: *mut ffi::sqlite3_context) {
+        let len = self.len() as libc::c_int;
+        match CString::new(self) {
+            Ok(cstring) => {
+                ffi::sqlite3_result_text(ctx, cstring.as_ptr(), len, ffi::SQLITE_TRANSIENT());
+            }
+            Err(_) => result_error(ctx, "function result contained an interior NUL byte"),
+        }
+    }
+}
+
+impl ToFunctionResult for i64 {
+    unsafe fn set_result(self, ctx: *mut ffi::sqlite3_context) {
+        ffi::sqlite3_result_int64(ctx, self);
+    }
+}
+
+impl ToFunctionResult for f64 {
+    unsafe fn set_result(self, ctx: *mut ffi::sqlite3_context) {
+        ffi::sqlite3_result_double(ctx, self);
+    }
+}
+
+/// Registers `f` as the scalar SQL function `fn_name`, taking `num_args`
+/// arguments. This is the implementation behind
+/// [`SqliteConnection::create_scalar_function`](../struct.SqliteConnection.html#method.create_scalar_function).
+///
+/// `f` is boxed and its ownership handed to SQLite as the function's user
+/// data; SQLite calls back into the `destroy` trampoline to drop it when the
+/// function is redefined or the connection closes, so no leak is possible as
+/// long as `sqlite3_create_function_v2` itself succeeds.
+pub fn register<F, Output>(conn: &RawConnection, fn_name: &str, num_args: i32, f: F) -> QueryResult<()>
+where
+    F: Fn(&[SqliteValue]) -> QueryResult<Output> + Send + 'static,
+    Output: ToFunctionResult,
+{
+    let fn_name = try!(CString::new(fn_name));
+    let user_data = Box::into_raw(Box::new(f)) as *mut libc::c_void;
+
+    let result = unsafe {
+        ffi::sqlite3_create_function_v2(
+            conn.internal_connection.as_ptr(),
+            fn_name.as_ptr(),
+            num_args as libc::c_int,
+            ffi::SQLITE_UTF8,
+            user_data,
+            Some(run::<F, Output>),
+            None,
+            None,
+            Some(destroy::<F>),
+        )
+    };
+
+    if result == ffi::SQLITE_OK {
+        Ok(())
+    } else {
+        unsafe { destroy::<F>(user_data) };
+        Err(Error::DatabaseError(
+            DatabaseErrorKind::__Unknown,
+            Box::new(conn.error_message()),
+        ))
+    }
+}
+
+extern "C" fn run<F, Output>(
+    ctx: *mut ffi::sqlite3_context,
+    num_args: libc::c_int,
+    args: *mut *mut ffi::sqlite3_value,
+) where
+    F: Fn(&[SqliteValue]) -> QueryResult<Output> + Send + 'static,
+    Output: ToFunctionResult,
+{
+    unsafe {
+        let f = &*(ffi::sqlite3_user_data(ctx) as *const F);
+
+        let args = (0..num_args as isize)
+            .filter_map(|i| SqliteValue::new(*args.offset(i)))
+            .collect::<Vec<_>>();
+
+        match panic::catch_unwind(AssertUnwindSafe(|| f(&args))) {
+            Ok(Ok(value)) => value.set_result(ctx),
+            Ok(Err(e)) => result_error(ctx, &e.to_string()),
+            Err(_) => result_error(ctx, "user-defined function panicked"),
+        }
+    }
+}
+
+unsafe fn result_error(ctx: *mut ffi::sqlite3_context, message: &str) {
+    if let Ok(message) = CString::new(message) {
+        ffi::sqlite3_result_error(ctx, message.as_ptr(), -1);
+    }
+}
+
+extern "C" fn destroy<F>(data: *mut libc::c_void) {
+    unsafe {
+        drop(Box::from_raw(data as *mut F));
+    }
+}
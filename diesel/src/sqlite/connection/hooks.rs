@@ -0,0 +1,124 @@
+extern crate libsqlite3_sys as ffi;
+
+use std::ffi::CStr;
+use std::os::raw as libc;
+
+use super::raw::RawConnection;
+
+/// The kind of row-level change reported to an update hook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeAction {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl ChangeAction {
+    fn from_raw(action: libc::c_int) -> Option<Self> {
+        match action {
+            ffi::SQLITE_INSERT => Some(ChangeAction::Insert),
+            ffi::SQLITE_UPDATE => Some(ChangeAction::Update),
+            ffi::SQLITE_DELETE => Some(ChangeAction::Delete),
+            _ => None,
+        }
+    }
+}
+
+pub type UpdateHookFn = Fn(ChangeAction, &str, &str, i64) + Send;
+pub type CommitHookFn = Fn() -> bool + Send;
+pub type RollbackHookFn = Fn() + Send;
+
+/// Registers `hook` with `sqlite3_update_hook` and returns the boxed
+/// closure. `sqlite3_update_hook` has no destructor argument, so the
+/// returned box must be kept alive by the caller (stored on
+/// `SqliteConnection`) for as long as the hook should remain installed;
+/// dropping it before calling this again or closing the connection would
+/// leave a dangling user-data pointer.
+pub fn set_update_hook<F>(conn: &RawConnection, hook: F) -> Box<Box<UpdateHookFn>>
+where
+    F: Fn(ChangeAction, &str, &str, i64) + Send + 'static,
+{
+    let boxed: Box<Box<UpdateHookFn>> = Box::new(Box::new(hook));
+    let user_data = &*boxed as *const Box<UpdateHookFn> as *mut libc::c_void;
+
+    unsafe {
+        ffi::sqlite3_update_hook(
+            conn.internal_connection.as_ptr(),
+            Some(update_trampoline),
+            user_data,
+        );
+    }
+
+    boxed
+}
+
+pub fn set_commit_hook<F>(conn: &RawConnection, hook: F) -> Box<Box<CommitHookFn>>
+where
+    F: Fn() -> bool + Send + 'static,
+{
+    let boxed: Box<Box<CommitHookFn>> = Box::new(Box::new(hook));
+    let user_data = &*boxed as *const Box<CommitHookFn> as *mut libc::c_void;
+
+    unsafe {
+        ffi::sqlite3_commit_hook(
+            conn.internal_connection.as_ptr(),
+            Some(commit_trampoline),
+            user_data,
+        );
+    }
+
+    boxed
+}
+
+pub fn set_rollback_hook<F>(conn: &RawConnection, hook: F) -> Box<Box<RollbackHookFn>>
+where
+    F: Fn() + Send + 'static,
+{
+    let boxed: Box<Box<RollbackHookFn>> = Box::new(Box::new(hook));
+    let user_data = &*boxed as *const Box<RollbackHookFn> as *mut libc::c_void;
+
+    unsafe {
+        ffi::sqlite3_rollback_hook(
+            conn.internal_connection.as_ptr(),
+            Some(rollback_trampoline),
+            user_data,
+        );
+    }
+
+    boxed
+}
+
+extern "C" fn update_trampoline(
+    p_arg: *mut libc::c_void,
+    action: libc::c_int,
+    db_name: *const libc::c_char,
+    table_name: *const libc::c_char,
+    rowid: i64,
+) {
+    unsafe {
+        let hook = &*(p_arg as *const Box<UpdateHookFn>);
+        if let Some(action) = ChangeAction::from_raw(action) {
+            let db_name = CStr::from_ptr(db_name).to_string_lossy();
+            let table_name = CStr::from_ptr(table_name).to_string_lossy();
+            hook(action, &db_name, &table_name, rowid);
+        }
+    }
+}
+
+extern "C" fn commit_trampoline(p_arg: *mut libc::c_void) -> libc::c_int {
+    unsafe {
+        let hook = &*(p_arg as *const Box<CommitHookFn>);
+        if hook() {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+extern "C" fn rollback_trampoline(p_arg: *mut libc::c_void) {
+    unsafe {
+        let hook = &*(p_arg as *const Box<RollbackHookFn>);
+        hook();
+    }
+}
@@ -2,12 +2,22 @@ extern crate libsqlite3_sys as ffi;
 
 #[doc(hidden)]
 pub mod raw;
+mod backup;
+mod blob;
+mod busy;
+mod functions;
+mod hooks;
 mod stmt;
 mod statement_iterator;
 mod sqlite_value;
 
+pub use self::backup::BackupProgress;
+pub use self::blob::Blob;
+pub use self::functions::ToFunctionResult;
+pub use self::hooks::ChangeAction;
 pub use self::sqlite_value::SqliteValue;
 
+use std::cell::RefCell;
 use std::time::{Instant, Duration};
 use std::os::raw as libc;
 use std::rc::Rc;
@@ -17,10 +27,12 @@ use query_builder::*;
 use query_builder::bind_collector::RawBytesBindCollector;
 use query_source::*;
 use result::*;
+use self::busy::BusyHandlerFn;
+use self::hooks::{CommitHookFn, RollbackHookFn, UpdateHookFn};
 use self::raw::RawConnection;
 use self::statement_iterator::StatementIterator;
 use self::stmt::{Statement, StatementUse};
-use sqlite::{Sqlite, SqliteQueryBuilder};
+use sqlite::{ErrorHook, Sqlite, SqliteQueryBuilder};
 use types::HasSqlType;
 
 #[allow(missing_debug_implementations)]
@@ -30,6 +42,16 @@ pub struct SqliteConnection {
     transaction_manager: AnsiTransactionManager,
     is_log_query: bool,
     is_explain_query: bool,
+    max_bind_params: usize,
+    // Kept alive only so the hook remains valid; SQLite holds a raw pointer
+    // into these boxes for the life of the connection. `RefCell`-wrapped,
+    // like `RawConnection`'s own `error_hook`, so installing a hook only
+    // needs `&self` and doesn't force callers holding a shared
+    // `&SqliteConnection` to restructure for it.
+    update_hook: RefCell<Option<Box<Box<UpdateHookFn>>>>,
+    commit_hook: RefCell<Option<Box<Box<CommitHookFn>>>>,
+    rollback_hook: RefCell<Option<Box<Box<RollbackHookFn>>>>,
+    busy_handler: RefCell<Option<Box<Box<BusyHandlerFn>>>>,
 }
 
 // This relies on the invariant that RawConnection or Statement are never
@@ -49,13 +71,23 @@ impl Connection for SqliteConnection {
 
     fn establish(database_url: &str, config: Config) -> ConnectionResult<Self> {
         let password = config.password.clone();
+        let statement_cache_capacity = config.statement_cache_capacity;
+        let busy_timeout_ms = config.busy_timeout_ms;
         RawConnection::establish(database_url, password).map(|conn| {
+            if let Some(busy_timeout_ms) = busy_timeout_ms {
+                self::busy::set_busy_timeout(&conn, busy_timeout_ms);
+            }
             SqliteConnection {
-                statement_cache: StatementCache::new(),
+                statement_cache: StatementCache::new(statement_cache_capacity),
                 raw_connection: Rc::new(conn),
                 transaction_manager: AnsiTransactionManager::new(),
                 is_log_query: config.is_log_query,
                 is_explain_query: config.is_explain_query,
+                max_bind_params: config.max_bind_params,
+                update_hook: RefCell::new(None),
+                commit_hook: RefCell::new(None),
+                rollback_hook: RefCell::new(None),
+                busy_handler: RefCell::new(None),
             }
         })
     }
@@ -153,6 +185,196 @@ impl SqliteConnection {
         }
     }
 
+    /// Installs a per-connection error hook, invoked with the SQLite error
+    /// code whenever a call on this connection fails. Takes precedence over
+    /// the process-wide [`init_error_hook`](fn.init_error_hook.html).
+    pub fn set_error_hook(&self, hook: Box<ErrorHook>) {
+        self.raw_connection.set_error_hook(hook);
+    }
+
+    /// Calls `hook` for every row inserted, updated, or deleted while this
+    /// connection is open, receiving the change kind, database name, table
+    /// name, and affected rowid. Useful for cache invalidation and
+    /// change-tracking.
+    pub fn set_update_hook<F>(&self, hook: F)
+    where
+        F: Fn(self::hooks::ChangeAction, &str, &str, i64) + Send + 'static,
+    {
+        *self.update_hook.borrow_mut() =
+            Some(self::hooks::set_update_hook(&self.raw_connection, hook));
+    }
+
+    /// Calls `hook` immediately before a transaction on this connection
+    /// commits. Returning `false` aborts the commit and triggers a
+    /// rollback instead.
+    pub fn set_commit_hook<F>(&self, hook: F)
+    where
+        F: Fn() -> bool + Send + 'static,
+    {
+        *self.commit_hook.borrow_mut() =
+            Some(self::hooks::set_commit_hook(&self.raw_connection, hook));
+    }
+
+    /// Calls `hook` whenever a transaction on this connection rolls back.
+    pub fn set_rollback_hook<F>(&self, hook: F)
+    where
+        F: Fn() + Send + 'static,
+    {
+        *self.rollback_hook.borrow_mut() =
+            Some(self::hooks::set_rollback_hook(&self.raw_connection, hook));
+    }
+
+    /// Runs `build_query(chunk)` once per chunk of `values` and
+    /// concatenates the results in order, so a predicate like
+    /// `eq_any(values)` over a large slice doesn't blow past SQLite's
+    /// `SQLITE_MAX_VARIABLE_NUMBER` (~999) by binding them all in a single
+    /// query.
+    ///
+    /// Chunks are sized per `Config::max_bind_params` and run with their
+    /// natural length -- a dynamic `eq_any(Vec)`/`IN` predicate has no
+    /// static `QueryId`, so each chunk is prepared uncached regardless
+    /// (see `queries_containing_in_with_vec_are_not_cached`); there is no
+    /// benefit to padding the final, possibly-short chunk to a uniform
+    /// size, only wasted bound parameters.
+    pub fn load_in_chunks<T, Q, U, F>(&self, values: &[T], mut build_query: F) -> QueryResult<Vec<U>>
+    where
+        T: Clone,
+        F: FnMut(&[T]) -> Q,
+        Q: AsQuery,
+        Q::Query: QueryFragment<Sqlite> + QueryId,
+        Sqlite: HasSqlType<Q::SqlType>,
+        U: Queryable<Q::SqlType, Sqlite>,
+    {
+        let chunk_size = self.max_bind_params;
+        if chunk_size == 0 || values.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut results = Vec::with_capacity(values.len());
+        for chunk in values.chunks(chunk_size) {
+            let query = build_query(chunk);
+            let mut rows = try!(self.query_by_index(query));
+            results.append(&mut rows);
+        }
+        Ok(results)
+    }
+
+    /// Sets how long, in milliseconds, operations on this connection retry
+    /// before giving up with `SQLITE_BUSY` when another connection holds a
+    /// write lock. Overrides `Config::busy_timeout_ms` and replaces any
+    /// handler installed with `set_busy_handler`.
+    pub fn set_busy_timeout(&self, ms: u32) {
+        self::busy::set_busy_timeout(&self.raw_connection, ms);
+    }
+
+    /// Installs a custom busy-handler, called with the number of prior
+    /// retries whenever this connection would otherwise return
+    /// `SQLITE_BUSY`. Returning `false` aborts the retry loop immediately;
+    /// returning `true` retries again. Replaces any timeout set with
+    /// `set_busy_timeout`.
+    pub fn set_busy_handler<F>(&self, handler: F)
+    where
+        F: Fn(i32) -> bool + Send + 'static,
+    {
+        *self.busy_handler.borrow_mut() =
+            Some(self::busy::set_busy_handler(&self.raw_connection, handler));
+    }
+
+    /// Opens a streaming handle onto a single BLOB/TEXT column value for
+    /// `rowid` in `table`, without loading it fully into memory. The
+    /// returned [`Blob`](struct.Blob.html) implements `Read`, `Write`, and
+    /// `Seek`; pass `read_write = true` to allow writes.
+    pub fn open_blob(
+        &self,
+        table: &str,
+        column: &str,
+        rowid: i64,
+        read_write: bool,
+    ) -> QueryResult<self::blob::Blob> {
+        self::blob::Blob::open(self.raw_connection.clone(), table, column, rowid, read_write)
+    }
+
+    /// Registers `f` as a custom SQL scalar function named `fn_name`, taking
+    /// `num_args` arguments, usable directly inside Diesel-built queries and
+    /// `sql::<...>` fragments.
+    ///
+    /// ```rust,ignore
+    /// conn.create_scalar_function("my_upper", 1, |args: &[SqliteValue]| -> QueryResult<String> {
+    ///     Ok(args[0].read_text().to_uppercase())
+    /// })?;
+    /// ```
+    pub fn create_scalar_function<F, Output>(
+        &self,
+        fn_name: &str,
+        num_args: i32,
+        f: F,
+    ) -> QueryResult<()>
+    where
+        F: Fn(&[SqliteValue]) -> QueryResult<Output> + Send + 'static,
+        Output: self::functions::ToFunctionResult,
+    {
+        self::functions::register(&self.raw_connection, fn_name, num_args, f)
+    }
+
+    /// Copies this database to the file at `dest_path`, using SQLite's
+    /// online backup API, without blocking other readers of this
+    /// connection.
+    ///
+    /// `pages_per_step` controls how much work is done per
+    /// `sqlite3_backup_step` call; a negative value copies the whole
+    /// database in one step, a positive value copies incrementally and lets
+    /// other connections interleave writes between steps. `progress`, if
+    /// given, is called after every step with the pages remaining and the
+    /// total page count.
+    ///
+    /// `dest_path` must name a file, not `:memory:` -- the destination
+    /// connection opened here is private and closed the instant this
+    /// function returns, so a `:memory:` backup would be unreachable. To
+    /// back up into (or restore from) a connection you're keeping open,
+    /// including one opened as `:memory:`, use
+    /// [`backup_into`](#method.backup_into) or
+    /// [`restore_from`](#method.restore_from) instead.
+    pub fn backup_to(
+        &self,
+        dest_path: &str,
+        pages_per_step: i32,
+        progress: Option<&mut FnMut(self::backup::BackupProgress)>,
+    ) -> QueryResult<()> {
+        let destination = try!(RawConnection::establish(dest_path, None).map_err(|e| {
+            Error::DatabaseError(
+                DatabaseErrorKind::__Unknown,
+                Box::new(e.to_string()),
+            )
+        }));
+        self::backup::backup(&self.raw_connection, &destination, pages_per_step, progress)
+    }
+
+    /// Copies this database into the already-open connection `dest`,
+    /// without blocking other readers of this connection. Unlike
+    /// `backup_to`, `dest` stays open and reachable by the caller after
+    /// this returns, so this is the way to snapshot into a `:memory:`
+    /// connection kept around for later use.
+    pub fn backup_into(
+        &self,
+        dest: &SqliteConnection,
+        pages_per_step: i32,
+        progress: Option<&mut FnMut(self::backup::BackupProgress)>,
+    ) -> QueryResult<()> {
+        self::backup::backup(&self.raw_connection, &dest.raw_connection, pages_per_step, progress)
+    }
+
+    /// Restores this connection's database from `source`, the reverse of
+    /// `backup_into`. Useful for loading a file-backed connection into
+    /// this connection's `:memory:` database.
+    pub fn restore_from(
+        &self,
+        source: &SqliteConnection,
+        pages_per_step: i32,
+        progress: Option<&mut FnMut(self::backup::BackupProgress)>,
+    ) -> QueryResult<()> {
+        self::backup::backup(&source.raw_connection, &self.raw_connection, pages_per_step, progress)
+    }
+
     /// Return String
     /// Elements in each row are separated by delimiter
     /// Rows are separated by `\n`
@@ -234,7 +456,7 @@ mod tests {
     use dsl::sql;
     use prelude::*;
     use super::*;
-    use types::Integer;
+    use types::{Integer, Text};
 
     #[test]
     fn prepared_statements_are_cached_when_run() {
@@ -285,6 +507,102 @@ mod tests {
         assert_eq!(1, connection.statement_cache.len());
     }
 
+    #[test]
+    fn statement_cache_respects_capacity_across_distinct_query_shapes() {
+        let config = Config::builder().statement_cache_capacity(2).build();
+        let connection = SqliteConnection::establish(":memory:", config).unwrap();
+
+        let int_query = ::select(AsExpression::<Integer>::as_expression(1));
+        let bool_query = ::select(AsExpression::<Integer>::as_expression(1).eq(2));
+        let text_query = ::select(AsExpression::<Text>::as_expression("x"));
+
+        assert_eq!(Ok(1), int_query.get_result(&connection));
+        assert_eq!(Ok(false), bool_query.get_result(&connection));
+        assert_eq!(2, connection.statement_cache.len());
+
+        // A third distinct query shape must evict something to stay within
+        // capacity, rather than growing the cache further.
+        assert_eq!(Ok("x".to_string()), text_query.get_result(&connection));
+        assert_eq!(2, connection.statement_cache.len());
+
+        // The evicted statement can still be reprepared and run correctly.
+        assert_eq!(Ok(1), int_query.get_result(&connection));
+        assert_eq!(2, connection.statement_cache.len());
+    }
+
+    #[test]
+    fn rollback_savepoint_leaves_the_enclosing_transaction_usable() {
+        let connection = SqliteConnection::establish(":memory:", Config::default()).unwrap();
+        connection.batch_execute("CREATE TABLE t (id INTEGER)").unwrap();
+
+        let result = connection.transaction::<_, Error, _>(|| {
+            try!(connection.execute("INSERT INTO t (id) VALUES (1)"));
+
+            let _ = connection.savepoint::<(), Error, _>(|| {
+                try!(connection.execute("INSERT INTO t (id) VALUES (2)"));
+                Err(Error::RollbackTransaction)
+            });
+
+            try!(connection.execute("INSERT INTO t (id) VALUES (3)"));
+            Ok(())
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(0, connection.transaction_manager().get_transaction_depth());
+
+        let count = connection
+            .execute_for_string("SELECT COUNT(*) FROM t", "")
+            .unwrap();
+        assert_eq!("2", count);
+    }
+
+    #[test]
+    fn load_in_chunks_matches_loading_all_ids_in_one_query() {
+        table! {
+            load_in_chunks_test_table (id) {
+                id -> Integer,
+            }
+        }
+        use self::load_in_chunks_test_table::dsl::*;
+
+        let mut connection = SqliteConnection::establish(":memory:", Config::default()).unwrap();
+        connection
+            .batch_execute("CREATE TABLE load_in_chunks_test_table (id INTEGER NOT NULL)")
+            .unwrap();
+        for id_value in 1..11 {
+            connection
+                .execute(&format!(
+                    "INSERT INTO load_in_chunks_test_table (id) VALUES ({})",
+                    id_value
+                ))
+                .unwrap();
+        }
+
+        let ids: Vec<i32> = (1..11).collect();
+
+        let expected = load_in_chunks_test_table
+            .filter(id.eq_any(ids.clone()))
+            .order(id)
+            .load::<i32>(&connection)
+            .unwrap();
+
+        // A chunk size that doesn't evenly divide the id list, so the
+        // final chunk is short.
+        connection.max_bind_params = 3;
+        let mut seen_chunk_lens = Vec::new();
+        let mut chunked = connection
+            .load_in_chunks(&ids, |chunk| {
+                seen_chunk_lens.push(chunk.len());
+                load_in_chunks_test_table.filter(id.eq_any(chunk.to_vec()))
+            })
+            .unwrap();
+        chunked.sort();
+
+        assert_eq!(expected, chunked);
+        // The final chunk runs at its natural, unpadded length.
+        assert_eq!(vec![3, 3, 3, 1], seen_chunk_lens);
+    }
+
     #[test]
     fn test_execute_for_string1() {
         let connection = SqliteConnection::establish(":memory:", Config::default()).unwrap();
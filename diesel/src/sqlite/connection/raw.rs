@@ -0,0 +1,171 @@
+extern crate libsqlite3_sys as ffi;
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw as libc;
+use std::ptr;
+use std::ptr::NonNull;
+
+use result::{ConnectionError, ConnectionResult, DatabaseErrorKind, Error, QueryResult};
+use super::{on_error, ErrorHook};
+
+#[allow(missing_debug_implementations)]
+pub struct RawConnection {
+    pub internal_connection: NonNull<ffi::sqlite3>,
+    error_hook: RefCell<Option<Box<ErrorHook>>>,
+}
+
+impl RawConnection {
+    pub fn establish(database_url: &str, password: Option<String>) -> ConnectionResult<Self> {
+        let database_url = try!(CString::new(database_url).map_err(|_| {
+            ConnectionError::InvalidConnectionUrl(database_url.into())
+        }));
+        let mut conn_pointer = ptr::null_mut();
+        let connection_status =
+            unsafe { ffi::sqlite3_open(database_url.as_ptr(), &mut conn_pointer) };
+
+        match NonNull::new(conn_pointer) {
+            Some(conn_pointer) if connection_status == ffi::SQLITE_OK => {
+                let raw_connection = RawConnection {
+                    internal_connection: conn_pointer,
+                    error_hook: RefCell::new(None),
+                };
+                if let Some(password) = password {
+                    try!(raw_connection.rekey(&password));
+                }
+                Ok(raw_connection)
+            }
+            Some(conn_pointer) => {
+                let error_message = error_message(conn_pointer.as_ptr());
+                unsafe { ffi::sqlite3_close(conn_pointer.as_ptr()) };
+                Err(ConnectionError::BadConnection(error_message))
+            }
+            None => Err(ConnectionError::BadConnection(
+                "sqlite3_open returned a null pointer".into(),
+            )),
+        }
+    }
+
+    pub fn exec(&self, query: &str) -> QueryResult<()> {
+        let query = try!(CString::new(query));
+        unsafe {
+            let callback_fn = None;
+            let callback_arg = ptr::null_mut();
+            let mut err_msg = ptr::null_mut();
+            ffi::sqlite3_exec(
+                self.internal_connection.as_ptr(),
+                query.as_ptr(),
+                callback_fn,
+                callback_arg,
+                &mut err_msg,
+            );
+            self.ensure_sqlite_ok(ffi::sqlite3_errcode(self.internal_connection.as_ptr()))
+        }
+    }
+
+    /// Installs a per-connection error hook, invoked whenever a call on this
+    /// connection returns a non-`SQLITE_OK` result code. This takes
+    /// precedence over the process-wide hook set via
+    /// [`init_error_hook`](../fn.init_error_hook.html), which remains as a
+    /// fallback for connections that don't set their own.
+    pub fn set_error_hook(&self, hook: Box<ErrorHook>) {
+        *self.error_hook.borrow_mut() = Some(hook);
+    }
+
+    pub fn rekey(&self, password: &str) -> QueryResult<libc::c_int> {
+        let password = try!(CString::new(password));
+        let result_code = unsafe {
+            ffi::sqlite3_rekey(
+                self.internal_connection.as_ptr(),
+                password.as_ptr() as *const libc::c_void,
+                password.as_bytes().len() as libc::c_int,
+            )
+        };
+        Ok(result_code)
+    }
+
+    pub fn execute_for_string(&self, query: &str, delimiter: &str) -> QueryResult<String> {
+        // Rows are collected through a raw sqlite3_exec callback: each row's
+        // column values (NULL as an empty string) are joined with
+        // `delimiter`, and the rows are joined with `\n`.
+        let query = try!(CString::new(query));
+        let delimiter = delimiter.to_owned();
+        let mut rows: Vec<String> = Vec::new();
+        extern "C" fn callback(
+            arg: *mut libc::c_void,
+            n_columns: libc::c_int,
+            values: *mut *mut libc::c_char,
+            _columns: *mut *mut libc::c_char,
+        ) -> libc::c_int {
+            let data = unsafe { &mut *(arg as *mut (Vec<String>, String)) };
+            let (ref mut rows, ref delimiter) = *data;
+            let mut row = Vec::with_capacity(n_columns as usize);
+            for i in 0..n_columns as isize {
+                let value = unsafe { *values.offset(i) };
+                let value = if value.is_null() {
+                    String::new()
+                } else {
+                    unsafe { CStr::from_ptr(value).to_string_lossy().into_owned() }
+                };
+                row.push(value);
+            }
+            rows.push(row.join(delimiter));
+            0
+        }
+
+        let mut data = (rows, delimiter);
+        unsafe {
+            let mut err_msg = ptr::null_mut();
+            ffi::sqlite3_exec(
+                self.internal_connection.as_ptr(),
+                query.as_ptr(),
+                Some(callback),
+                &mut data as *mut _ as *mut libc::c_void,
+                &mut err_msg,
+            );
+            try!(self.ensure_sqlite_ok(ffi::sqlite3_errcode(self.internal_connection.as_ptr())));
+        }
+        rows = data.0;
+        Ok(rows.join("\n"))
+    }
+
+    pub fn rows_affected_by_last_query(&self) -> usize {
+        unsafe { ffi::sqlite3_changes(self.internal_connection.as_ptr()) as usize }
+    }
+
+    /// The text of the most recent error reported against this connection.
+    pub fn error_message(&self) -> String {
+        error_message(self.internal_connection.as_ptr())
+    }
+
+    fn ensure_sqlite_ok(&self, code: libc::c_int) -> QueryResult<()> {
+        if code == ffi::SQLITE_OK {
+            Ok(())
+        } else {
+            match *self.error_hook.borrow() {
+                Some(ref hook) => hook.on_error(code),
+                None => on_error(code),
+            }
+            let message = self.error_message();
+            Err(Error::DatabaseError(
+                DatabaseErrorKind::__Unknown,
+                Box::new(message),
+            ))
+        }
+    }
+}
+
+impl Drop for RawConnection {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::sqlite3_close(self.internal_connection.as_ptr());
+        }
+    }
+}
+
+fn error_message(conn: *mut ffi::sqlite3) -> String {
+    unsafe {
+        let message = ffi::sqlite3_errmsg(conn);
+        CStr::from_ptr(message).to_string_lossy().into_owned()
+    }
+}
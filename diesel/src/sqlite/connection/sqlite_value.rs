@@ -0,0 +1,60 @@
+extern crate libsqlite3_sys as ffi;
+
+use std::ffi::CStr;
+use std::os::raw as libc;
+use std::slice;
+use std::str;
+
+/// A wrapper around a raw `sqlite3_value*`, as received from either a
+/// statement's result columns or the argument array of a user-defined SQL
+/// function.
+///
+/// This does not own the pointer it wraps -- SQLite owns the underlying
+/// `sqlite3_value`, and it is only valid for the duration of the call that
+/// handed it to us (a row fetch, or a function invocation).
+#[allow(missing_debug_implementations)]
+pub struct SqliteValue {
+    value: *mut ffi::sqlite3_value,
+}
+
+impl SqliteValue {
+    pub unsafe fn new(value: *mut ffi::sqlite3_value) -> Option<Self> {
+        if value.is_null() {
+            None
+        } else {
+            Some(SqliteValue { value: value })
+        }
+    }
+
+    pub fn read_integer(&self) -> i64 {
+        unsafe { ffi::sqlite3_value_int64(self.value) }
+    }
+
+    pub fn read_real(&self) -> f64 {
+        unsafe { ffi::sqlite3_value_double(self.value) }
+    }
+
+    pub fn read_text(&self) -> &str {
+        unsafe {
+            let ptr = ffi::sqlite3_value_text(self.value) as *const libc::c_char;
+            let cstr = CStr::from_ptr(ptr);
+            str::from_utf8_unchecked(cstr.to_bytes())
+        }
+    }
+
+    pub fn read_blob(&self) -> &[u8] {
+        unsafe {
+            let ptr = ffi::sqlite3_value_blob(self.value);
+            let len = ffi::sqlite3_value_bytes(self.value);
+            if ptr.is_null() || len == 0 {
+                &[]
+            } else {
+                slice::from_raw_parts(ptr as *const u8, len as usize)
+            }
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        unsafe { ffi::sqlite3_value_type(self.value) == ffi::SQLITE_NULL }
+    }
+}
@@ -30,6 +30,12 @@ fn on_error(code: i32) {
 }
 
 /// init error hook, must init first
+///
+/// This is a process-wide fallback. A mutable `static` shared across every
+/// connection and thread is a soundness hazard once more than one
+/// `SqliteConnection` is in play; prefer
+/// [`SqliteConnection::set_error_hook`](struct.SqliteConnection.html#method.set_error_hook),
+/// which stores the hook on the connection it applies to.
 pub fn init_error_hook(err_hook: Box<Arc<ErrorHook>>) {
     unsafe {
         ERROR_HOOK = Some(err_hook);